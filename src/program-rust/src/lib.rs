@@ -1,119 +1,401 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    program::invoke,
-    system_instruction,
     account_info::{next_account_info, AccountInfo},
     entrypoint,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
 };
 
 entrypoint!(process_instruction);
 
-// Define the PensionSystem struct to hold contract data
+/// Seed prefix for a contributor's per-account pension PDA.
+const PENSION_SEED: &[u8] = b"pension";
+
+/// Load/save helper for account-backed Borsh state. `load` deserializes an
+/// account's data buffer into `Self`, `save` serializes it back in place,
+/// and `save_exempt` additionally refuses to write unless the account stays
+/// rent-exempt at its current size.
+trait BorshState: Sized + BorshSerialize + BorshDeserialize {
+    fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
+        Self::try_from_slice(&account.data.borrow()).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    fn save(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        let data = self
+            .try_to_vec()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let mut dst = account.data.borrow_mut();
+        if dst.len() != data.len() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        dst.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn save_exempt(&self, account: &AccountInfo, rent: &Rent) -> Result<(), ProgramError> {
+        let data = self
+            .try_to_vec()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if !rent.is_exempt(account.lamports(), data.len()) {
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        self.save(account)
+    }
+}
+
+// Define the PensionSystem struct to hold contract-wide data. Per-contributor
+// balances and plans now live in their own PDA (see `ContributorState`)
+// instead of growing unbounded vectors here.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
 struct PensionSystem {
     owner: Pubkey,
-    balances: Vec<(Pubkey, u64)>,
-    pension_plan: Vec<(Pubkey, u64)>,
     total_balance: u64,
 }
 
-// Define the PensionPlan struct to hold the contributors
+impl BorshState for PensionSystem {}
+
+/// Per-contributor state, held in a PDA derived from `[PENSION_SEED,
+/// contributor.key]`. The `bump` found by `find_program_address` when the
+/// account is created is persisted here; `verify_pda` still re-derives the
+/// address (and bump) on every call to check the passed account.
+#[derive(Debug, Default, BorshSerialize, BorshDeserialize)]
+struct ContributorState {
+    balance: u64,
+    plan: u64,
+    contributed: u64,
+    bump: u8,
+}
+
+impl BorshState for ContributorState {}
+
+impl ContributorState {
+    /// Derives the contributor's PDA and checks that `contributor_account`
+    /// is that exact address.
+    fn verify_pda(
+        program_id: &Pubkey,
+        contributor: &Pubkey,
+        contributor_account: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        let (expected, bump) =
+            Pubkey::find_program_address(&[PENSION_SEED, contributor.as_ref()], program_id);
+        if &expected != contributor_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(bump)
+    }
+
+    /// Loads an already-provisioned contributor PDA. Used by the contributor
+    /// handlers other than `contribute`, which is the only one allowed to
+    /// provision a contributor's account in the first place, and
+    /// `close_contributor`, which loads the account itself.
+    fn load_existing(
+        program_id: &Pubkey,
+        contributor: &Pubkey,
+        contributor_account: &AccountInfo,
+    ) -> Result<Self, ProgramError> {
+        Self::verify_pda(program_id, contributor, contributor_account)?;
+        if contributor_account.data_is_empty() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Self::load(contributor_account)
+    }
+
+    /// Loads the contributor's PDA, allocating and rent-funding it from
+    /// `payer` via `invoke_signed` the first time this contributor is seen.
+    /// The bump derived here is stored in the new state.
+    fn load_or_create<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        contributor: &Pubkey,
+        contributor_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+    ) -> Result<Self, ProgramError> {
+        let bump = Self::verify_pda(program_id, contributor, contributor_account)?;
+
+        if !contributor_account.data_is_empty() {
+            return Self::load(contributor_account);
+        }
+
+        let fresh = Self {
+            balance: 0,
+            plan: 0,
+            contributed: 0,
+            bump,
+        };
+        let space = fresh
+            .try_to_vec()
+            .map_err(|_| ProgramError::InvalidAccountData)?
+            .len() as u64;
+        let rent = Rent::get()?;
+        let lamports = rent.minimum_balance(space as usize);
+
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                contributor_account.key,
+                lamports,
+                space,
+                program_id,
+            ),
+            &[payer.clone(), contributor_account.clone(), system_program.clone()],
+            &[&[PENSION_SEED, contributor.as_ref(), &[bump]]],
+        )?;
+
+        Ok(fresh)
+    }
+}
+
+/// Errors specific to the pension program, returned instead of a generic
+/// `ProgramError::BorshIoError` string so clients can match on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+enum PensionError {
+    #[error("sender did not sign the instruction")]
+    MissingRequiredSignature,
+    #[error("sender is not authorized to perform this operation")]
+    Unauthorized,
+    #[error("arithmetic overflow or underflow on a lamport amount")]
+    MathOverflow,
+    #[error("pension payout exceeds the contributor's contributions or the system's total balance")]
+    LedgerInvariantViolated,
+}
+
+impl From<PensionError> for ProgramError {
+    fn from(e: PensionError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+/// Typed instruction set for the pension program, decoded in one shot with
+/// `try_from_slice` instead of hand-rolled byte-offset parsing.
 #[derive(Debug, BorshDeserialize, BorshSerialize)]
-struct PensionPlan {
-    owner: Pubkey,
-    balances: Vec<(Pubkey, u64)>,
-    total_balance: u64,
+enum PensionInstruction {
+    Contribute { amount: u64 },
+    SetPensionPlan { amount: u64 },
+    GetPension,
+    GetBalance,
+    GetTotalBalance,
+    GetOwner,
+    SetOwner { new_owner: Pubkey },
+    UpdatePensionPlan { amount: u64 },
+    CloseContributor,
 }
 
 impl PensionSystem {
     fn new(owner: Pubkey) -> Self {
         Self {
             owner,
-            balances: Vec::new(),
-            pension_plan: Vec::new(),
             total_balance: 0,
         }
     }
 
-    fn contribute(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
-        let accounts_iter = &mut accounts.iter();
-        let sender = next_account_info(accounts_iter)?;
-    
+    // Solana instruction handlers naturally thread one parameter per account
+    // plus the sysvars they need; that's an inherent cost of the account
+    // model, not a sign this should be broken up.
+    #[allow(clippy::too_many_arguments)]
+    fn contribute<'a>(
+        &mut self,
+        sender: &AccountInfo<'a>,
+        contributor_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+        rent: &Rent,
+        amount: u64,
+    ) -> ProgramResult {
         if amount == 0 {
-            return Err(ProgramError::BorshIoError("Contribution amount must be greater than zero.".to_string()));
-        }
-    
-        let mut pension_plan = PensionPlan::try_from_slice(&sender.data.borrow())?;
-        
-        if let Some((_, balance)) = pension_plan.balances.iter_mut().find(|(pubkey, _)| pubkey == &sender.key) {
-            *balance += amount;
-        } else {
-            pension_plan.balances.push((sender.key, amount));
+            return Err(ProgramError::BorshIoError(
+                "Contribution amount must be greater than zero.".to_string(),
+            ));
         }
-    
-        let transfer_instruction = system_instruction::transfer(sender.key, &pension_plan.owner, amount);
+
+        let mut contributor = ContributorState::load_or_create(
+            program_id,
+            sender,
+            sender.key,
+            contributor_account,
+            system_program,
+        )?;
+        contributor.balance = contributor
+            .balance
+            .checked_add(amount)
+            .ok_or(PensionError::MathOverflow)?;
+        contributor.contributed = contributor
+            .contributed
+            .checked_add(amount)
+            .ok_or(PensionError::MathOverflow)?;
+
+        let transfer_instruction = system_instruction::transfer(sender.key, &self.owner, amount);
         invoke(&transfer_instruction, accounts)?;
-    
-        pension_plan.total_balance += amount;
-    
+
+        self.total_balance = self
+            .total_balance
+            .checked_add(amount)
+            .ok_or(PensionError::MathOverflow)?;
+
         msg!("New contribution from {:?}: {:?}", sender.key, amount);
-    
-        pension_plan.serialize(&mut &mut sender.data.borrow_mut()[..])?;
-    
-        Ok(())
+
+        contributor.save_exempt(contributor_account, rent)
     }
-    
-    
-    fn set_pension_plan(&mut self, sender: &Pubkey, amount: u64) -> ProgramResult {
+
+    fn set_pension_plan(
+        &mut self,
+        sender: &Pubkey,
+        contributor_account: &AccountInfo,
+        program_id: &Pubkey,
+        rent: &Rent,
+        amount: u64,
+    ) -> ProgramResult {
         if amount == 0 {
-            return Err(solana_program::program_error::ProgramError::BorshIoError("Pension plan amount must be greater than zero.".to_string()));
+            return Err(ProgramError::BorshIoError(
+                "Pension plan amount must be greater than zero.".to_string(),
+            ));
         }
 
-        if let Some((_, balance)) = self.balances.iter_mut().find(|(pubkey, _)| pubkey == sender) {
-            if *balance < amount {
-                return Err(solana_program::program_error::ProgramError::BorshIoError("Insufficient balance to set pension plan amount.".to_string()));
-            }
-            *balance -= amount;
+        let mut contributor = ContributorState::load_existing(program_id, sender, contributor_account)?;
+
+        contributor.balance = contributor
+            .balance
+            .checked_sub(amount)
+            .ok_or(ProgramError::BorshIoError(
+                "Insufficient balance to set pension plan amount.".to_string(),
+            ))?;
+        contributor.plan = amount;
+
+        msg!("New pension plan set for {:?}: {:?}", sender, amount);
+
+        contributor.save_exempt(contributor_account, rent)
+    }
+
+    fn update_pension_plan(
+        &mut self,
+        sender: &Pubkey,
+        contributor_account: &AccountInfo,
+        program_id: &Pubkey,
+        rent: &Rent,
+        amount: u64,
+    ) -> ProgramResult {
+        let mut contributor = ContributorState::load_existing(program_id, sender, contributor_account)?;
+
+        // Moving the delta between the old and new plan between `balance`
+        // and `plan` keeps the same rule as `set_pension_plan`: an increase
+        // must be covered by the contributor's current `balance`. A plan
+        // above the cumulative contribution is refused by `get_pension`.
+        if amount > contributor.plan {
+            let increase = amount - contributor.plan;
+            contributor.balance = contributor.balance.checked_sub(increase).ok_or(
+                ProgramError::BorshIoError(
+                    "Insufficient balance to increase pension plan amount.".to_string(),
+                ),
+            )?;
         } else {
-            return Err(solana_program::program_error::ProgramError::BorshIoError("No balance found for the given sender address.".to_string()));
+            let decrease = contributor.plan - amount;
+            contributor.balance = contributor
+                .balance
+                .checked_add(decrease)
+                .ok_or(PensionError::MathOverflow)?;
         }
+        contributor.plan = amount;
 
-        if let Some((_, plan)) = self.pension_plan.iter_mut().find(|(pubkey, _)| pubkey == sender) {
-            *plan = amount;
-        } else {
-            self.pension_plan.push((*sender, amount));
+        msg!("Pension plan updated for {:?}: {:?}", sender, amount);
+
+        contributor.save_exempt(contributor_account, rent)
+    }
+
+    /// Closes a contributor's PDA once its balance is fully drawn down,
+    /// zeroing the account data and returning its rent to the contributor.
+    fn close_contributor(
+        sender: &AccountInfo,
+        contributor_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        ContributorState::verify_pda(program_id, sender.key, contributor_account)?;
+        let contributor = ContributorState::load(contributor_account).unwrap_or_default();
+
+        if contributor.balance != 0 {
+            return Err(ProgramError::BorshIoError(
+                "Cannot close a contributor account with a nonzero balance.".to_string(),
+            ));
         }
 
-        msg!("New pension plan set for {:?}: {:?}", sender, amount);
+        contributor_account.data.borrow_mut().fill(0);
+
+        let rent_lamports = contributor_account.lamports();
+        **contributor_account.lamports.borrow_mut() = contributor_account
+            .lamports()
+            .checked_sub(rent_lamports)
+            .ok_or(PensionError::MathOverflow)?;
+        **sender.lamports.borrow_mut() = sender
+            .lamports()
+            .checked_add(rent_lamports)
+            .ok_or(PensionError::MathOverflow)?;
+
+        msg!("Closed contributor account for {:?}", sender.key);
         Ok(())
     }
 
-    fn get_pension(&mut self, sender: &Pubkey) -> ProgramResult {
-        if let Some((_, plan)) = self.pension_plan.iter().find(|(pubkey, _)| pubkey == sender) {
-            if *plan == 0 {
-                return Err(solana_program::program_error::ProgramError::BorshIoError("No pension plan set for this address.".to_string()));
-            }
+    fn get_pension(
+        &mut self,
+        sender: &Pubkey,
+        contributor_account: &AccountInfo,
+        program_id: &Pubkey,
+        rent: &Rent,
+    ) -> ProgramResult {
+        let mut contributor = ContributorState::load_existing(program_id, sender, contributor_account)?;
 
-            if self.total_balance < *plan {
-                return Err(solana_program::program_error::ProgramError::BorshIoError("Insufficient funds to pay pension.".to_string()));
-            }
+        if contributor.plan == 0 {
+            return Err(ProgramError::BorshIoError(
+                "No pension plan set for this address.".to_string(),
+            ));
+        }
 
-            self.balances.iter_mut().find(|(pubkey, _)| pubkey == sender).unwrap().1 += *plan;
-            self.total_balance -= *plan;
+        if self.total_balance < contributor.plan {
+            return Err(ProgramError::BorshIoError(
+                "Insufficient funds to pay pension.".to_string(),
+            ));
+        }
 
-            msg!("Pension paid to {:?}: {:?}", sender, *plan);
-        } else {
-            return Err(solana_program::program_error::ProgramError::BorshIoError("No pension plan found for the given sender address.".to_string()));
+        // Balances now live in per-contributor PDAs, so the sum across all
+        // contributors can't be checked against `total_balance` at this call
+        // site. Cap the payout against this contributor's own cumulative
+        // contribution instead, so nobody can inflate their pension payout
+        // with other contributors' share of the shared pool.
+        if contributor.plan > contributor.contributed {
+            return Err(PensionError::LedgerInvariantViolated.into());
         }
 
-        Ok(())
+        let paid_balance = contributor
+            .balance
+            .checked_add(contributor.plan)
+            .ok_or(PensionError::MathOverflow)?;
+        if paid_balance > self.total_balance {
+            return Err(PensionError::LedgerInvariantViolated.into());
+        }
+
+        contributor.balance = paid_balance;
+        self.total_balance = self
+            .total_balance
+            .checked_sub(contributor.plan)
+            .ok_or(PensionError::MathOverflow)?;
+
+        msg!("Pension paid to {:?}: {:?}", sender, contributor.plan);
+
+        contributor.save_exempt(contributor_account, rent)
     }
 
-    fn get_balance(&self, sender: &Pubkey) -> ProgramResult {
-        let balance = self.balances.iter().find(|(pubkey, _)| pubkey == sender).map(|(_, balance)| *balance).unwrap_or(0);
-        msg!("Balance of {:?}: {:?}", sender, balance);
+    fn get_balance(
+        &self,
+        sender: &Pubkey,
+        contributor_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let contributor = ContributorState::load_existing(program_id, sender, contributor_account)?;
+        msg!("Balance of {:?}: {:?}", sender, contributor.balance);
         Ok(())
     }
 
@@ -129,7 +411,7 @@ impl PensionSystem {
 
     fn set_owner(&mut self, sender: &Pubkey, new_owner: &Pubkey) -> ProgramResult {
         if sender != &self.owner {
-            return Err(solana_program::program_error::ProgramError::BorshIoError("Only the owner can set a new owner.".to_string()));
+            return Err(PensionError::Unauthorized.into());
         }
 
         self.owner = *new_owner;
@@ -148,27 +430,70 @@ fn process_instruction(
     let account_info_iter = &mut accounts.iter();
     let owner = next_account_info(account_info_iter)?;
     let sender = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let contributor_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
 
-    let mut pension_system = PensionSystem::new(*owner.key);
+    if !sender.is_signer {
+        return Err(PensionError::MissingRequiredSignature.into());
+    }
+
+    let mut pension_system =
+        PensionSystem::load(state_account).unwrap_or_else(|_| PensionSystem::new(*owner.key));
+
+    let rent = Rent::get()?;
+    let instruction = PensionInstruction::try_from_slice(instruction_data)?;
 
-    match instruction_data[0] {
-        0 => {
-            let amount = instruction_data[1..9].iter().fold(0, |acc, x| (acc << 8) + *x as u64);
-            pension_system.contribute(sender, amount)
+    // Pure queries and `CloseContributor` never mutate `pension_system`, so
+    // they don't need to pay for a rewrite of `state_account` (and don't need
+    // it writable at all).
+    let needs_save = !matches!(
+        &instruction,
+        PensionInstruction::GetOwner
+            | PensionInstruction::GetBalance
+            | PensionInstruction::GetTotalBalance
+            | PensionInstruction::CloseContributor
+    );
+
+    let result = match instruction {
+        PensionInstruction::Contribute { amount } => pension_system.contribute(
+            sender,
+            contributor_account,
+            system_program,
+            accounts,
+            program_id,
+            &rent,
+            amount,
+        ),
+        PensionInstruction::SetPensionPlan { amount } => {
+            pension_system.set_pension_plan(sender.key, contributor_account, program_id, &rent, amount)
+        }
+        PensionInstruction::GetPension => {
+            pension_system.get_pension(sender.key, contributor_account, program_id, &rent)
         }
-        1 => {
-            let amount = instruction_data[1..9].iter().fold(0, |acc, x| (acc << 8) + *x as u64);
-            pension_system.set_pension_plan(sender, amount)
+        PensionInstruction::GetBalance => {
+            pension_system.get_balance(sender.key, contributor_account, program_id)
         }
-        2 => pension_system.get_pension(sender),
-        3 => pension_system.get_balance(sender),
-        4 => pension_system.get_total_balance(),
-        5 => pension_system.get_owner(),
-        6 => {
-            let new_owner = Pubkey::new(&instruction_data[1..33]);
-            pension_system.set_owner(sender, &new_owner)
+        PensionInstruction::GetTotalBalance => pension_system.get_total_balance(),
+        PensionInstruction::GetOwner => pension_system.get_owner(),
+        PensionInstruction::SetOwner { new_owner } => pension_system.set_owner(sender.key, &new_owner),
+        PensionInstruction::UpdatePensionPlan { amount } => pension_system.update_pension_plan(
+            sender.key,
+            contributor_account,
+            program_id,
+            &rent,
+            amount,
+        ),
+        PensionInstruction::CloseContributor => {
+            PensionSystem::close_contributor(sender, contributor_account, program_id)
         }
-        _ => Err(solana_program::program_error::ProgramError::BorshIoError("Invalid instruction.".to_string())),
+    };
+
+    result?;
+
+    if needs_save {
+        pension_system.save_exempt(state_account, &rent)?;
     }
-}
 
+    Ok(())
+}